@@ -1,11 +1,15 @@
+use futures_util::{SinkExt, StreamExt};
 use lazy_static::lazy_static;
 use log::{debug, error, info};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::error::Error;
-use std::sync::RwLock;
-use std::time::Duration;
+use std::fmt;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 lazy_static! {
     static ref DEVICE_STATUS: RwLock<DeviceStatus> = RwLock::new(DeviceStatus::new());
@@ -27,6 +31,190 @@ struct JsonRpcResponse {
     id: u32,
 }
 
+/// Which sink `start_update_status` streams snapshots to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportMode {
+    /// Short-lived HTTP POSTs per interval (the original behavior).
+    #[default]
+    Http,
+    /// One long-lived WebSocket connection, see [`start_update_status_ws`].
+    Ws,
+    /// Publish each snapshot to a NATS subject for fan-out to many consumers.
+    Nats,
+}
+
+fn default_interval() -> u64 {
+    1
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_bandwidth_window() -> usize {
+    30
+}
+
+fn default_spill_cap() -> usize {
+    1000
+}
+
+/// Runtime configuration loaded from a TOML file, replacing the raw
+/// `url`/`interval` arguments and the hard-coded timeout and bandwidth window.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// Telemetry server endpoint: an HTTP URL, a WebSocket URL or a NATS
+    /// server address, matching `transport`.
+    pub server_url: String,
+    /// Interval between snapshots, in seconds.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Per-request timeout for the HTTP transport, in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Number of one-minute slots kept in each bandwidth ring buffer.
+    #[serde(default = "default_bandwidth_window")]
+    pub bandwidth_window: usize,
+    /// Newest-first retention cap for the on-disk spill queue, so a long
+    /// outage can't grow it without bound.
+    #[serde(default = "default_spill_cap")]
+    pub spill_cap: usize,
+    /// Transport backend to stream snapshots over.
+    #[serde(default)]
+    pub transport: TransportMode,
+    /// Static device owner, applied to `DEVICE_STATUS` on start.
+    #[serde(default)]
+    pub device_owner: String,
+    /// Static device version, applied to `DEVICE_STATUS` on start.
+    #[serde(default)]
+    pub device_version: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server_url: String::new(),
+            interval: default_interval(),
+            timeout: default_timeout(),
+            bandwidth_window: default_bandwidth_window(),
+            spill_cap: default_spill_cap(),
+            transport: TransportMode::default(),
+            device_owner: String::new(),
+            device_version: String::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Start from defaults with only the server endpoint set; chain the
+    /// `with_*` setters to override individual fields.
+    pub fn new(server_url: impl Into<String>) -> Self {
+        Self {
+            server_url: server_url.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Override the snapshot interval, in seconds.
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Override the transport backend.
+    pub fn with_transport(mut self, transport: TransportMode) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Read and parse a TOML configuration file.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&contents).map_err(ConfigError::Parse)
+    }
+}
+
+/// Failure reading or parsing a [`Config`] file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file was read but did not parse as valid TOML.
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "read config file failed: {}", e),
+            ConfigError::Parse(e) => write!(f, "parse config file failed: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+        }
+    }
+}
+
+/// Exponential-backoff-with-jitter policy for consecutive send failures.
+///
+/// While the failure counter is zero the caller keeps its steady-state
+/// interval; after each failure the next attempt is delayed by
+/// `min(base * factor^n, max_delay)` plus random jitter so many devices
+/// reporting to one server don't reconnect in lock-step.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    /// Delay after the first failure.
+    pub base: Duration,
+    /// Multiplier applied per consecutive failure.
+    pub factor: u32,
+    /// Upper bound on the (pre-jitter) delay.
+    pub max_delay: Duration,
+    failures: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(1),
+            factor: 2,
+            max_delay: Duration::from_secs(60),
+            failures: 0,
+        }
+    }
+}
+
+impl Backoff {
+    /// Reset the failure counter so operation resumes at the steady-state
+    /// interval.
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Record a consecutive failure and return how long to sleep before the
+    /// next attempt, including jitter in `[0, delay/2)`.
+    pub fn record_failure(&mut self) -> Duration {
+        let n = self.failures;
+        self.failures = self.failures.saturating_add(1);
+        let delay = self
+            .base
+            .saturating_mul(self.factor.saturating_pow(n))
+            .min(self.max_delay);
+        let half = delay / 2;
+        if half.is_zero() {
+            delay
+        } else {
+            delay + half.mul_f64(rand::random::<f64>())
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 struct DeviceStatus {
     device_id: String,
@@ -39,6 +227,18 @@ struct DeviceStatus {
     finalized_block_number: u32,
     upload_bandwidth: Vec<u64>,
     download_bandwidth: Vec<u64>,
+    #[serde(default)]
+    upload_rate_current: u64,
+    #[serde(default)]
+    upload_rate_avg: u64,
+    #[serde(default)]
+    upload_rate_peak: u64,
+    #[serde(default)]
+    download_rate_current: u64,
+    #[serde(default)]
+    download_rate_avg: u64,
+    #[serde(default)]
+    download_rate_peak: u64,
     uptime: i64,
     monitor_type: u8,
     monitor_sync_chains: Vec<(u32, u32)>,
@@ -54,6 +254,44 @@ impl DeviceStatus {
     }
 }
 
+/// Bytes/sec of the most recently completed one-minute slot. The current slot
+/// is the last element and is still filling, so it is skipped.
+fn current_rate(slots: &[u64]) -> u64 {
+    match slots.len() {
+        0 | 1 => 0,
+        n => slots[n - 2] / 60,
+    }
+}
+
+/// Rolling average rate in bytes/sec over all completed slots in the window,
+/// skipping the still-filling current slot.
+fn average_rate(slots: &[u64]) -> u64 {
+    let completed = match slots.len() {
+        0 | 1 => return 0,
+        n => &slots[..n - 1],
+    };
+    completed.iter().sum::<u64>() / completed.len() as u64 / 60
+}
+
+/// Peak completed-slot rate in bytes/sec over the window.
+fn peak_rate(slots: &[u64]) -> u64 {
+    match slots.len() {
+        0 | 1 => 0,
+        n => slots[..n - 1].iter().copied().max().unwrap_or(0) / 60,
+    }
+}
+
+/// Derive the per-window bandwidth-rate fields from the ring buffers already
+/// carried by the snapshot, so nothing extra has to be tracked.
+fn fill_bandwidth_rates(device: &mut DeviceStatus) {
+    device.upload_rate_current = current_rate(&device.upload_bandwidth);
+    device.upload_rate_avg = average_rate(&device.upload_bandwidth);
+    device.upload_rate_peak = peak_rate(&device.upload_bandwidth);
+    device.download_rate_current = current_rate(&device.download_bandwidth);
+    device.download_rate_avg = average_rate(&device.download_bandwidth);
+    device.download_rate_peak = peak_rate(&device.download_bandwidth);
+}
+
 async fn update_status(
     client: &Client,
     url: &str,
@@ -97,6 +335,193 @@ async fn get_status(client: &Client, url: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+static SPILL_TREE: OnceLock<sled::Tree> = OnceLock::new();
+
+/// A buffered snapshot captured while the server was unreachable, stamped with
+/// the wall-clock time it was taken so the server can order replays.
+#[derive(Serialize, Deserialize, Debug)]
+struct BufferedStatus {
+    timestamp: u64,
+    status: DeviceStatus,
+}
+
+/// Lazily open the sled tree that backs the offline spill queue.
+fn spill_tree() -> Option<&'static sled::Tree> {
+    if let Some(tree) = SPILL_TREE.get() {
+        return Some(tree);
+    }
+    match sled::open("telemetry_spill").and_then(|db| db.open_tree("status")) {
+        Ok(tree) => {
+            // Ignore the error from a lost init race; the winner's tree is returned.
+            let _ = SPILL_TREE.set(tree);
+            SPILL_TREE.get()
+        }
+        Err(e) => {
+            error!("open telemetry spill queue failed with error: {}", e);
+            None
+        }
+    }
+}
+
+/// Serialize a snapshot and append it to the spill queue keyed by a
+/// monotonically increasing sequence number, trimming the oldest entries once
+/// the queue exceeds `cap` (from [`Config::spill_cap`]).
+fn buffer_status(status: &DeviceStatus, cap: usize) {
+    let Some(tree) = spill_tree() else { return };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let buffered = BufferedStatus {
+        timestamp,
+        status: status.clone(),
+    };
+    let bytes = match serde_json::to_vec(&buffered) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            debug!("serialize spilled status failed with error: {}", e);
+            return;
+        }
+    };
+    let key = tree.generate_id().unwrap_or(0).to_be_bytes();
+    if let Err(e) = tree.insert(key, bytes) {
+        debug!("append spilled status failed with error: {}", e);
+        return;
+    }
+    // Drop the oldest entries beyond the cap.
+    while tree.len() > cap {
+        match tree.pop_min() {
+            Ok(Some(_)) => {}
+            _ => break,
+        }
+    }
+}
+
+/// Replay a single buffered snapshot to the server as a `replay_status`
+/// JSON-RPC call, keeping the server's acknowledgement semantics identical to
+/// `update_status`.
+async fn replay_status(
+    client: &Client,
+    url: &str,
+    buffered: &BufferedStatus,
+) -> Result<(), Box<dyn Error>> {
+    let request = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "replay_status".to_string(),
+        params: json!(buffered),
+        id: 1,
+    };
+
+    let response: JsonRpcResponse = client.post(url).json(&request).send().await?.json().await?;
+
+    if let Some(error) = response.error {
+        error!("Error: {:?}", error);
+    }
+
+    Ok(())
+}
+
+/// Drain the spill queue oldest-first, replaying each buffered snapshot and
+/// deleting its key only once the server acknowledges. Stops at the first
+/// failure so the remaining entries survive for the next attempt.
+async fn drain_spill_queue(client: &Client, url: &str) -> Result<(), Box<dyn Error>> {
+    let Some(tree) = spill_tree() else { return Ok(()) };
+    while let Some((key, bytes)) = tree.first()? {
+        let buffered: BufferedStatus = serde_json::from_slice(&bytes)?;
+        replay_status(client, url, &buffered).await?;
+        tree.remove(&key)?;
+    }
+    Ok(())
+}
+
+/// Replay any buffered snapshots, then send the live one; on any failure the
+/// live snapshot is spilled to disk so no status history is lost during an
+/// outage.
+async fn send_with_spill(
+    client: &Client,
+    url: &str,
+    device: &DeviceStatus,
+    cap: usize,
+) -> Result<(), Box<dyn Error>> {
+    if let Err(e) = drain_spill_queue(client, url).await {
+        buffer_status(device, cap);
+        return Err(e);
+    }
+    if let Err(e) = update_status(client, url, device).await {
+        buffer_status(device, cap);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Clone the current [`DEVICE_STATUS`] with bandwidth rates filled in, or
+/// `None` when the mandatory identity fields aren't populated yet and the
+/// snapshot should be skipped. Shared by every transport's interval tick so
+/// the collection logic and skip guard can't drift between backends.
+fn collect_snapshot() -> Option<DeviceStatus> {
+    let mut device = DEVICE_STATUS.read().unwrap().clone();
+    fill_bandwidth_rates(&mut device);
+    if device.device_id.is_empty() || device.device_owner.is_empty() || device.peer_id.is_empty() {
+        debug!("skip update status");
+        return None;
+    }
+    Some(device)
+}
+
+/// A snapshot sink selected by [`Config::transport`]. The interval/backoff
+/// loop in [`start_update_status`] is identical regardless of backend; only
+/// this sink changes.
+enum Sink {
+    Http {
+        client: Client,
+        url: String,
+        spill_cap: usize,
+    },
+    Nats {
+        client: async_nats::Client,
+    },
+}
+
+impl Sink {
+    /// Establish the transport for the configured backend.
+    async fn connect(config: &Config) -> Result<Self, Box<dyn Error>> {
+        match config.transport {
+            TransportMode::Nats => {
+                let client = async_nats::connect(&config.server_url).await?;
+                Ok(Sink::Nats { client })
+            }
+            _ => {
+                let client = Client::builder()
+                    .timeout(Duration::from_secs(config.timeout))
+                    .build()?;
+                Ok(Sink::Http {
+                    client,
+                    url: config.server_url.clone(),
+                    spill_cap: config.spill_cap,
+                })
+            }
+        }
+    }
+
+    /// Send one snapshot over the backend.
+    async fn send(&self, device: &DeviceStatus) -> Result<(), Box<dyn Error>> {
+        match self {
+            Sink::Http {
+                client,
+                url,
+                spill_cap,
+            } => send_with_spill(client, url, device, *spill_cap).await,
+            Sink::Nats { client } => {
+                let subject = format!("telemetry.status.{}", device.device_id);
+                let payload = serde_json::to_vec(device)?;
+                client.publish(subject, payload.into()).await?;
+                client.flush().await?;
+                Ok(())
+            }
+        }
+    }
+}
+
 fn start_calculate_bandwidth() {
     {
         let mut device = DEVICE_STATUS.write().unwrap();
@@ -119,26 +544,149 @@ fn start_calculate_bandwidth() {
     });
 }
 
-pub async fn start_update_status(url: &str, interval: u64) {
+pub async fn start_update_status(config: &Config) {
+    set_bandwidth_window(config.bandwidth_window);
+    if !config.device_owner.is_empty() {
+        set_device_owner(config.device_owner.clone());
+    }
+    if !config.device_version.is_empty() {
+        set_device_version(config.device_version.clone());
+    }
+    if config.transport == TransportMode::Ws {
+        start_update_status_ws(&config.server_url, config.interval).await;
+        return;
+    }
     start_calculate_bandwidth();
-    let mut interval = tokio::time::interval(Duration::from_secs(interval));
-    let client = Client::builder().timeout(Duration::from_secs(30)).build().unwrap();
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+    let sink = match Sink::connect(config).await {
+        Ok(sink) => sink,
+        Err(e) => {
+            error!("connect telemetry sink failed with error: {}", e);
+            return;
+        }
+    };
+    let mut backoff = Backoff::default();
     loop {
         interval.tick().await;
-        let device = DEVICE_STATUS.read().unwrap().clone();
-        if !device.device_id.is_empty() && !device.device_owner.is_empty()
-        && !device.peer_id.is_empty()
-            {
-            if let Err(e) = update_status(&client, url, &device).await {
-                debug!("update status to telemetry failed with error: {}",e);
+        let Some(device) = collect_snapshot() else {
+            continue;
+        };
+        match sink.send(&device).await {
+            Ok(()) => backoff.record_success(),
+            Err(e) => {
+                debug!("update status to telemetry failed with error: {}", e);
                 error!("update status to telemetry failed");
+                tokio::time::sleep(backoff.record_failure()).await;
+            }
+        }
+    }
+}
+
+/// Keep one long-lived WebSocket connection to the telemetry server.
+///
+/// Unlike the HTTP transport of [`start_update_status`], which sends each
+/// snapshot as its own POST request and cannot receive server-initiated
+/// requests, this streams `update_status` JSON-RPC frames over a single
+/// socket and lets the server push `get_status` requests back down the same
+/// connection, answered with the current `DEVICE_STATUS` snapshot on demand.
+/// Frames are dispatched by their JSON-RPC `method` field.
+pub async fn start_update_status_ws(url: &str, interval: u64) {
+    start_calculate_bandwidth();
+    let mut interval = tokio::time::interval(Duration::from_secs(interval));
+    let mut backoff = Backoff::default();
+    // Reconnect for the life of the task: a dropped socket must not stop
+    // telemetry. Connect failures and unexpected drops both fall through to
+    // the same [`Backoff`] delay used by the HTTP path. The failure counter is
+    // only reset once a frame has actually been exchanged, so an accept-then-
+    // close server keeps backing off instead of spinning.
+    loop {
+        let (ws_stream, _) = match connect_async(url).await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("connect telemetry websocket failed with error: {}", e);
+                tokio::time::sleep(backoff.record_failure()).await;
+                continue;
+            }
+        };
+        let (mut write, mut read) = ws_stream.split();
+        // Serve the socket until it drops, then fall through to reconnect.
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Some(device) = collect_snapshot() {
+                        let request = JsonRpcRequest {
+                            jsonrpc: "2.0".to_string(),
+                            method: "update_status".to_string(),
+                            params: json!(device),
+                            id: 1,
+                        };
+                        let frame = Message::Text(serde_json::to_string(&request).unwrap());
+                        if let Err(e) = write.send(frame).await {
+                            debug!("push update status over websocket failed with error: {}", e);
+                            error!("push update status over websocket failed");
+                        }
+                    }
+                }
+                msg = read.next() => {
+                    match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            // A server frame proves the socket is live; the
+                            // connection has earned a reset of the backoff.
+                            backoff.record_success();
+                            let request: JsonRpcRequest = match serde_json::from_str(&text) {
+                                Ok(request) => request,
+                                Err(e) => {
+                                    debug!("decode websocket frame failed with error: {}", e);
+                                    continue;
+                                }
+                            };
+                            // Dispatch server-initiated frames by JSON-RPC method.
+                            match request.method.as_str() {
+                                "get_status" => {
+                                    let mut device = DEVICE_STATUS.read().unwrap().clone();
+                                    fill_bandwidth_rates(&mut device);
+                                    let response = JsonRpcResponse {
+                                        jsonrpc: "2.0".to_string(),
+                                        result: Some(json!(device)),
+                                        error: None,
+                                        id: request.id,
+                                    };
+                                    let frame = Message::Text(serde_json::to_string(&response).unwrap());
+                                    if let Err(e) = write.send(frame).await {
+                                        debug!("reply get_status over websocket failed with error: {}", e);
+                                    }
+                                }
+                                other => debug!("ignore unexpected websocket method: {}", other),
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | None => {
+                            info!("telemetry websocket closed; reconnecting");
+                            break;
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            error!("telemetry websocket read failed with error: {}", e);
+                            break;
+                        }
+                    }
+                }
             }
-        } else {
-            debug!("skip update status");
         }
+        // The socket dropped; back off before reconnecting. A connection that
+        // never exchanged a frame leaves the failure counter climbing, so an
+        // accept-then-close server is throttled rather than spun on.
+        tokio::time::sleep(backoff.record_failure()).await;
     }
 }
 
+/// Resize both bandwidth ring buffers to `window` one-minute slots, replacing
+/// the previous contents; used to apply `Config::bandwidth_window` at start.
+pub fn set_bandwidth_window(window: usize) {
+    let mut device = DEVICE_STATUS.write().unwrap();
+    device.upload_bandwidth = vec![0; window];
+    device.download_bandwidth = vec![0; window];
+}
+
 pub fn set_device_id(device_id: String) {
     DEVICE_STATUS.write().unwrap().device_id = device_id;
 }
@@ -210,11 +758,11 @@ mod tests {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
             let url = "http://127.0.0.1:3030";
-            let interval = 1;
+            let config = Config::new(url).with_interval(1);
 
             add_upload(100);
             add_download(1000);
-            tokio::spawn(async move { start_update_status(url, interval).await });
+            tokio::spawn(async move { start_update_status(&config).await });
 
             let client = Client::new();
 